@@ -0,0 +1,107 @@
+//! A convenience API for one-shot calls to C functions.
+//!
+//! Building a `Cif` by hand and calling through it is the right tool
+//! when a signature is going to be used many times, but for a single
+//! dynamic call it's more boilerplate than the call site deserves.
+//! `Builder` assembles the `Cif` for you from the argument and return
+//! types it's given and calls through it immediately.
+
+use low::CodePtr;
+use middle::{Arg, Cif, FfiType, Type};
+
+/// Builds up and makes a one-shot call to a C function.
+///
+/// ```ignore
+/// let n: i32 = unsafe { call::Builder::new(fun).arg(&1i32).arg(&2i32).returns() };
+/// ```
+pub struct Builder<'a> {
+    fun:        CodePtr,
+    arg_types:  Vec<Type>,
+    args:       Vec<Arg<'a>>,
+    nfixedargs: Option<usize>,
+}
+
+impl<'a> Builder<'a> {
+    /// Starts building a call to the function at `fun`.
+    pub fn new(fun: CodePtr) -> Self {
+        Builder {
+            fun:        fun,
+            arg_types:  Vec::new(),
+            args:       Vec::new(),
+            nfixedargs: None,
+        }
+    }
+
+    /// Adds an argument to the call.
+    pub fn arg<T: FfiType>(mut self, arg: &'a T) -> Self {
+        self.arg_types.push(T::reify());
+        self.args.push(Arg::new(arg));
+        self
+    }
+
+    /// Marks every argument added so far as a fixed argument and
+    /// every argument added after this call as part of the variadic
+    /// tail, as in a `printf`-style function. Without calling this,
+    /// the call is treated as non-variadic.
+    pub fn fixed(mut self) -> Self {
+        self.nfixedargs = Some(self.args.len());
+        self
+    }
+
+    /// Finishes the call, invoking the function and interpreting its
+    /// result as an `R`.
+    ///
+    /// # Safety
+    ///
+    /// `fun` must be callable with the argument `Type`s built up by
+    /// the preceding `arg` calls (and, if `fixed` was called, with a
+    /// variadic tail starting where it was called), and `R` must
+    /// match the C function's actual return type. Nothing here
+    /// checks any of that; getting it wrong is undefined behavior.
+    pub unsafe fn returns<R: FfiType>(self) -> R {
+        let cif = match self.nfixedargs {
+            None            => Cif::new(self.arg_types, R::reify()),
+            Some(nfixedargs) =>
+                Cif::new_variadic(self.arg_types, nfixedargs, R::reify()),
+        };
+
+        cif.call(self.fun, &self.args)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::os::raw::c_void;
+
+    #[test]
+    fn call_with_signed_and_float_args() {
+        // A negative signed argument and a float argument/return, to
+        // prove `Builder` threads argument types through correctly
+        // beyond the unsigned-integer case the other layers cover.
+        extern "C" fn scale(a: i32, b: f64) -> f64 { a as f64 * b }
+        let fun = CodePtr::from_ptr(scale as *mut c_void);
+
+        let (a, b): (i32, f64) = (-3, 2.5);
+        let result: f64 = unsafe { Builder::new(fun).arg(&a).arg(&b).returns() };
+
+        assert_eq!(-7.5, result);
+    }
+
+    #[test]
+    fn call_variadic_with_fixed() {
+        // Regression test for the variadic path: `fixed` must mark
+        // only the arguments added before it as fixed, leaving those
+        // added after it to be passed as the variadic tail via
+        // `Cif::new_variadic`.
+        extern "C" fn sum3(a: u64, b: u64, c: u64) -> u64 { a + b + c }
+        let fun = CodePtr::from_ptr(sum3 as *mut c_void);
+
+        let (a, b, c): (u64, u64, u64) = (1, 2, 3);
+        let result: u64 = unsafe {
+            Builder::new(fun).arg(&a).fixed().arg(&b).arg(&c).returns()
+        };
+
+        assert_eq!(6, result);
+    }
+}
@@ -0,0 +1,124 @@
+//! Typed closures turned into C function pointers.
+
+use std::os::raw::c_void;
+use std::ptr;
+
+use low;
+use low::CodePtr;
+use middle::{Cif, Closure, FfiType};
+
+macro_rules! define_closure {
+    ( $closure:ident, $callback:ident, $( $ty:ident ),* ) => {
+        /// Turns a Rust closure into a C function pointer that can be
+        /// handed to C code expecting a plain `extern "C" fn`.
+        ///
+        /// The underlying `ffi_closure` is freed, and the function
+        /// pointer invalidated, when this value is dropped.
+        pub struct $closure<'a, $($ty,)* R> {
+            closure:   Closure<'a>,
+            _userdata: Box<Box<Fn($($ty),*) -> R + 'a>>,
+        }
+
+        unsafe extern "C" fn $callback<'a, $($ty,)* R>(
+            _cif:     *mut low::ffi_cif,
+            result:   *mut c_void,
+            args:     *mut *mut c_void,
+            userdata: *mut Box<Fn($($ty),*) -> R + 'a>)
+        {
+            #[allow(unused_mut, unused_variables)]
+            let mut arg = args;
+            $(
+                // `*arg` points at a C-owned argument slot, not a Rust
+                // value we can move out of by naive deref (`$ty` isn't
+                // bounded by `Copy`), so read it out by value instead.
+                let $ty: $ty = ptr::read(*arg as *const $ty);
+                #[allow(unused_assignments)]
+                { arg = arg.offset(1); }
+            )*
+
+            let f = &*userdata;
+            *(result as *mut R) = f($($ty),*);
+        }
+
+        impl<'a, $($ty,)* R> $closure<'a, $($ty,)* R>
+            where $($ty: FfiType + 'a,)* R: FfiType + 'a
+        {
+            /// Constructs a closure callable from C, invoking `f`
+            /// when called.
+            pub fn new<Func>(f: Func) -> Self
+                where Func: Fn($($ty),*) -> R + 'a
+            {
+                let cif = Cif::new(vec![$($ty::reify()),*], R::reify());
+                let mut userdata: Box<Box<Fn($($ty),*) -> R + 'a>>
+                    = Box::new(Box::new(f));
+
+                // The boxed closure's heap address doesn't change when
+                // `userdata` is moved into `Self` below, so the pointer
+                // handed to libffi stays valid for as long as `Self` lives.
+                let closure = unsafe {
+                    Closure::new(cif, $callback::<$($ty,)* R>,
+                                 &mut *(&mut *userdata as *mut _))
+                };
+
+                $closure {
+                    closure:   closure,
+                    _userdata: userdata,
+                }
+            }
+
+            /// Gets the C function pointer for calling this closure.
+            pub fn code_ptr(&self) -> &CodePtr {
+                self.closure.code_ptr()
+            }
+        }
+    };
+}
+
+define_closure!(Closure0, callback0, );
+define_closure!(Closure1, callback1, A);
+define_closure!(Closure2, callback2, A, B);
+define_closure!(Closure3, callback3, A, B, C);
+define_closure!(Closure4, callback4, A, B, C, D);
+define_closure!(Closure5, callback5, A, B, C, D, E);
+define_closure!(Closure6, callback6, A, B, C, D, E, F);
+define_closure!(Closure7, callback7, A, B, C, D, E, F, G);
+define_closure!(Closure8, callback8, A, B, C, D, E, F, G, H);
+define_closure!(Closure9, callback9, A, B, C, D, E, F, G, H, I);
+define_closure!(Closure10, callback10, A, B, C, D, E, F, G, H, I, J);
+define_closure!(Closure11, callback11, A, B, C, D, E, F, G, H, I, J, K);
+define_closure!(Closure12, callback12, A, B, C, D, E, F, G, H, I, J, K, L);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn call_closure_at_max_arity() {
+        // Proves the macro-generated trampoline is correct at its
+        // widest arity (12), not just the two-argument case the
+        // other layers' tests happen to use.
+        let closure = Closure12::new(
+            |a: u64, b: u64, c: u64, d: u64, e: u64, f: u64,
+             g: u64, h: u64, i: u64, j: u64, k: u64, l: u64|
+                a + b + c + d + e + f + g + h + i + j + k + l);
+        let fun: &unsafe extern "C" fn(u64, u64, u64, u64, u64, u64,
+                                       u64, u64, u64, u64, u64, u64) -> u64
+            = unsafe { closure.code_ptr().as_fun() };
+
+        assert_eq!(78, unsafe { fun(1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12) });
+    }
+
+    #[test]
+    fn call_closure_with_narrow_return() {
+        // Regression test: libffi writes non-aggregate results through
+        // a full machine-word-sized slot, so a return type narrower
+        // than a word (here `u32`, vs. a 64-bit word) must still read
+        // back exactly, with no garbage in the unused high bytes.
+        let closure = Closure2::new(|a: u32, b: u32| a.wrapping_mul(b));
+        let fun: &unsafe extern "C" fn(u32, u32) -> u32
+            = unsafe { closure.code_ptr().as_fun() };
+
+        assert_eq!(42, unsafe { fun(6, 7) });
+        assert_eq!(u32::max_value(), unsafe { fun(u32::max_value(), 1) });
+    }
+}
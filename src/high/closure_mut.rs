@@ -0,0 +1,112 @@
+//! Typed closures over `FnMut`, turned into C function pointers.
+
+use std::os::raw::c_void;
+use std::ptr;
+
+use low;
+use low::CodePtr;
+use middle::{Cif, Closure, FfiType};
+
+macro_rules! define_closure_mut {
+    ( $closure:ident, $callback:ident, $( $ty:ident ),* ) => {
+        /// Like the corresponding `ClosureN`, but wraps an `FnMut`
+        /// closure, so the callback may mutate its captured state
+        /// across invocations.
+        pub struct $closure<'a, $($ty,)* R> {
+            closure:   Closure<'a>,
+            _userdata: Box<Box<FnMut($($ty),*) -> R + 'a>>,
+        }
+
+        unsafe extern "C" fn $callback<'a, $($ty,)* R>(
+            _cif:     *mut low::ffi_cif,
+            result:   *mut c_void,
+            args:     *mut *mut c_void,
+            userdata: *mut Box<FnMut($($ty),*) -> R + 'a>)
+        {
+            #[allow(unused_mut, unused_variables)]
+            let mut arg = args;
+            $(
+                // `*arg` points at a C-owned argument slot, not a Rust
+                // value we can move out of by naive deref (`$ty` isn't
+                // bounded by `Copy`), so read it out by value instead.
+                let $ty: $ty = ptr::read(*arg as *const $ty);
+                #[allow(unused_assignments)]
+                { arg = arg.offset(1); }
+            )*
+
+            let f = &mut *userdata;
+            *(result as *mut R) = f($($ty),*);
+        }
+
+        impl<'a, $($ty,)* R> $closure<'a, $($ty,)* R>
+            where $($ty: FfiType + 'a,)* R: FfiType + 'a
+        {
+            /// Constructs a closure callable from C, invoking `f`
+            /// when called. `f` may mutate its captured state.
+            pub fn new<Func>(f: Func) -> Self
+                where Func: FnMut($($ty),*) -> R + 'a
+            {
+                let cif = Cif::new(vec![$($ty::reify()),*], R::reify());
+                let mut userdata: Box<Box<FnMut($($ty),*) -> R + 'a>>
+                    = Box::new(Box::new(f));
+
+                let trampoline: low::CallbackMut<Box<FnMut($($ty),*) -> R + 'a>>
+                    = $callback::<$($ty,)* R>;
+
+                // The boxed closure's heap address doesn't change when
+                // `userdata` is moved into `Self` below, so the pointer
+                // handed to libffi stays valid for as long as `Self` lives.
+                let closure = unsafe {
+                    Closure::new(cif, trampoline, &mut *(&mut *userdata as *mut _))
+                };
+
+                $closure {
+                    closure:   closure,
+                    _userdata: userdata,
+                }
+            }
+
+            /// Gets the C function pointer for calling this closure.
+            pub fn code_ptr(&self) -> &CodePtr {
+                self.closure.code_ptr()
+            }
+        }
+    };
+}
+
+define_closure_mut!(ClosureMut0, callback_mut0, );
+define_closure_mut!(ClosureMut1, callback_mut1, A);
+define_closure_mut!(ClosureMut2, callback_mut2, A, B);
+define_closure_mut!(ClosureMut3, callback_mut3, A, B, C);
+define_closure_mut!(ClosureMut4, callback_mut4, A, B, C, D);
+define_closure_mut!(ClosureMut5, callback_mut5, A, B, C, D, E);
+define_closure_mut!(ClosureMut6, callback_mut6, A, B, C, D, E, F);
+define_closure_mut!(ClosureMut7, callback_mut7, A, B, C, D, E, F, G);
+define_closure_mut!(ClosureMut8, callback_mut8, A, B, C, D, E, F, G, H);
+define_closure_mut!(ClosureMut9, callback_mut9, A, B, C, D, E, F, G, H, I);
+define_closure_mut!(ClosureMut10, callback_mut10, A, B, C, D, E, F, G, H, I, J);
+define_closure_mut!(ClosureMut11, callback_mut11, A, B, C, D, E, F, G, H, I, J, K);
+define_closure_mut!(ClosureMut12, callback_mut12, A, B, C, D, E, F, G, H, I, J, K, L);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn call_mut_closure_mutates_captured_state() {
+        let mut total: u64 = 0;
+        let closure = ClosureMut1::new(move |x: u64| {
+            total += x;
+            total
+        });
+        let fun: &unsafe extern "C" fn(u64) -> u64
+            = unsafe { closure.code_ptr().as_fun() };
+
+        // Each call must see the effect of the previous one, proving
+        // the trampoline calls through as `FnMut` rather than
+        // re-reading a fixed snapshot.
+        assert_eq!(5, unsafe { fun(5) });
+        assert_eq!(9, unsafe { fun(4) });
+        assert_eq!(19, unsafe { fun(10) });
+    }
+}
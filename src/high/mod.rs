@@ -0,0 +1,16 @@
+//! A type-safe API for turning Rust closures into C function pointers.
+//!
+//! Each `ClosureN` wraps a Rust closure taking `N` arguments as a
+//! borrowable `extern "C" fn`, suitable for handing to C code that
+//! expects a plain function pointer. Building one monomorphizes a
+//! trampoline that reads its arguments out of libffi's `args` array,
+//! invokes the closure, and writes the result back — no manual
+//! `transmute`s or callback-writing required.
+
+mod closure;
+mod closure_mut;
+mod closure_once;
+
+pub use self::closure::*;
+pub use self::closure_mut::*;
+pub use self::closure_once::*;
@@ -4,8 +4,10 @@
 
 use c;
 
+use std::cmp;
 use std::mem;
 use std::os::raw::{c_void, c_uint};
+use std::ptr;
 
 /// The two kinds of errors reported by libffi.
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Copy, Clone)]
@@ -47,6 +49,42 @@ pub use c::ffi_type_complex_float;
 pub use c::ffi_type_complex_double;
 pub use c::ffi_type_complex_longdouble;
 
+pub use c::FFI_TYPE_STRUCT;
+
+/// Wraps a function pointer of unknown type.
+///
+/// This is used to represent the C `void(*)()`-style function pointers
+/// that libffi deals in, without committing to a particular Rust `fn`
+/// signature. Callers convert to and from the real type with
+/// [`CodePtr::as_fun`] and [`CodePtr::from_fun`], which centralizes the
+/// otherwise unavoidable `transmute`s in one audited place.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CodePtr(*mut c_void);
+
+impl CodePtr {
+    /// Constructs a `CodePtr` from a function pointer.
+    pub fn from_fun(fun: unsafe extern "C" fn()) -> Self {
+        CodePtr(fun as *mut c_void)
+    }
+
+    /// Constructs a `CodePtr` from a raw pointer.
+    pub fn from_ptr(fun: *mut c_void) -> Self {
+        CodePtr(fun)
+    }
+
+    /// Gets the code pointer as typed by `F`, which should be a
+    /// function pointer type.
+    pub unsafe fn as_fun<F>(&self) -> &F {
+        assert_eq!(mem::size_of::<F>(), mem::size_of::<Self>());
+        mem::transmute(&self.0)
+    }
+
+    /// Gets the code pointer as a `*mut c_void`.
+    pub fn as_ptr(&self) -> *mut c_void {
+        self.0
+    }
+}
+
 /// Initalizes a CIF (Call InterFace) with the given ABI and types.
 /// Note that the CIF retains references to `rtype` and `atypes`, so if
 /// they are no longer live when the CIF is used then the result is
@@ -81,23 +119,45 @@ pub unsafe fn prep_cif_var(cif: *mut ffi_cif,
 
 /// Calls a C function using the calling convention and types specified
 /// by the given CIF.
+///
+/// For non-aggregate return types, libffi always writes through a
+/// full machine-word-sized slot, even when `R` is narrower (e.g. a
+/// `u32` or `u8` return on a 64-bit target); the real value occupies
+/// the low-order bytes of that slot, which on a big-endian target are
+/// the *last* `size_of::<R>()` bytes, not the first. To avoid writing
+/// past a narrower `R`, while still reading the value back from the
+/// right end of the slot, the result is written into word-sized
+/// scratch space (a `Vec<usize>`, so it's word-aligned even for an `R`
+/// like `u64`/`f64`) and then read back out as `R` from the correct
+/// offset for the target's endianness.
 pub unsafe fn call<R>(cif:  *mut ffi_cif,
-                      fun:  extern "C" fn(),
+                      fun:  CodePtr,
                       args: *mut *mut c_void) -> R
 {
-    let mut result: R = mem::uninitialized();
-    c::ffi_call(cif, Some(fun), mem::transmute(&mut result as *mut R), args);
-    result
+    let word_size = mem::size_of::<usize>();
+    let words = (mem::size_of::<R>() + word_size - 1) / word_size;
+    let mut storage: Vec<usize> = vec![0usize; cmp::max(words, 1)];
+    c::ffi_call(cif, Some(mem::transmute(fun.as_ptr())),
+               storage.as_mut_ptr() as *mut c_void, args);
+
+    let offset = if mem::size_of::<R>() >= word_size {
+        0
+    } else if cfg!(target_endian = "little") {
+        0
+    } else {
+        word_size - mem::size_of::<R>()
+    };
+    ptr::read((storage.as_ptr() as *const u8).offset(offset as isize) as *const R)
 }
 
 /// Allocates a closure, returning a pair of the writable closure
 /// object and the function pointer for calling it.
-pub fn closure_alloc() -> (*mut ffi_closure, extern "C" fn()) {
+pub fn closure_alloc() -> (*mut ffi_closure, CodePtr) {
     unsafe {
         let mut code_pointer: *mut c_void = mem::uninitialized();
         let closure = c::ffi_closure_alloc(mem::size_of::<ffi_closure>(),
                                            &mut code_pointer);
-        (mem::transmute(closure), mem::transmute(code_pointer))
+        (mem::transmute(closure), CodePtr::from_ptr(code_pointer))
     }
 }
 
@@ -114,19 +174,32 @@ pub type Callback<U>
                            args:     *mut *mut c_void,
                            userdata: *mut U);
 
+/// Same signature as `Callback`, named separately for callbacks whose
+/// `userdata` they mutate or consume (e.g. an `FnMut` or `FnOnce`
+/// closure boxed as `U`) rather than merely reading it. `low` itself
+/// doesn't enforce the distinction — `prep_closure_loc` accepts either
+/// — but `high`'s `ClosureMutN`/`ClosureOnceN` type their trampolines
+/// as `CallbackMut` to document which access pattern they rely on.
+pub type CallbackMut<U>
+    = unsafe extern "C" fn(cif:      *mut ffi_cif,
+                           result:   *mut c_void,
+                           args:     *mut *mut c_void,
+                           userdata: *mut U);
+
+
 /// Prepares a closure to call the given callback function with the
 /// given user data.
 pub unsafe fn prep_closure_loc<U>(closure:  *mut ffi_closure,
                                   cif:      *mut ffi_cif,
                                   callback: Callback<U>,
                                   userdata: *mut U,
-                                  code:     extern "C" fn()) -> Result<()>
+                                  code:     CodePtr) -> Result<()>
 {
     let status = c::ffi_prep_closure_loc(closure,
                                          cif,
                                          Some(mem::transmute(callback)),
                                          mem::transmute(userdata),
-                                         mem::transmute(code));
+                                         mem::transmute(code.as_ptr()));
     ffi_status_to_result(status, ())
 }
 
@@ -158,14 +231,15 @@ mod test {
             prep_cif(&mut cif, c::FFI_DEFAULT_ABI, 1, &mut ffi_type_uint64,
                      args.as_mut_ptr()).unwrap();
 
-            let (closure, fun_) = closure_alloc();
-            let fun: unsafe extern "C" fn(u64) -> u64 = mem::transmute(fun_);
+            let (closure, code) = closure_alloc();
 
             prep_closure_loc(closure,
                              &mut cif,
                              callback,
                              mem::transmute(&mut env),
-                             mem::transmute(fun)).unwrap();
+                             code).unwrap();
+
+            let fun: &unsafe extern "C" fn(u64) -> u64 = code.as_fun();
 
             assert_eq!(11, fun(6));
             assert_eq!(12, fun(7));
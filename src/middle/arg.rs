@@ -0,0 +1,28 @@
+//! Call arguments.
+
+use std::marker::PhantomData;
+use std::os::raw::c_void;
+
+/// A reference to an argument to pass to `Cif::call`.
+///
+/// `Arg` borrows its referent, so a `Cif::call` using it cannot
+/// outlive the value it was built from.
+pub struct Arg<'a> {
+    value: *mut c_void,
+    _marker: PhantomData<&'a ()>,
+}
+
+impl<'a> Arg<'a> {
+    /// Captures a reference to `r` as a call argument.
+    pub fn new<T>(r: &'a T) -> Self {
+        Arg {
+            value: r as *const T as *mut c_void,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Gets the raw pointer to pass to `low::call`.
+    pub fn as_ptr(&self) -> *mut c_void {
+        self.value
+    }
+}
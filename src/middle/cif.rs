@@ -0,0 +1,133 @@
+//! Owned call interfaces.
+
+use std::cell::UnsafeCell;
+use std::os::raw::c_void;
+
+use low;
+use low::CodePtr;
+use middle::arg::Arg;
+use middle::types::Type;
+
+/// An owned `ffi_cif`, together with the argument and return `Type`s
+/// it was built from.
+///
+/// Keeping the `Type`s alongside the raw CIF ensures they outlive it,
+/// since libffi retains references into them for as long as the CIF
+/// is used. The same goes for `atypes`: `low::prep_cif` stores that
+/// raw pointer array in the CIF itself and dereferences it again on
+/// every call, so it must be kept alive here too, not just the `Type`
+/// handles it was built from.
+pub struct Cif {
+    cif: UnsafeCell<low::ffi_cif>,
+    // Never read again, but must outlive `cif` since libffi retains
+    // pointers into them.
+    #[allow(dead_code)]
+    atypes: Box<[*mut low::ffi_type]>,
+    #[allow(dead_code)]
+    args: Vec<Type>,
+    #[allow(dead_code)]
+    result: Type,
+}
+
+impl Cif {
+    /// Creates a new CIF for a function taking `args` and returning
+    /// `result`, using the platform's default calling convention.
+    pub fn new<I>(args: I, result: Type) -> Self
+        where I: IntoIterator<Item = Type>
+    {
+        let args: Vec<Type> = args.into_iter().collect();
+        let mut atypes: Box<[*mut low::ffi_type]>
+            = args.iter().map(Type::as_raw_ptr).collect();
+
+        let mut cif: low::ffi_cif = Default::default();
+        unsafe {
+            low::prep_cif(&mut cif,
+                          low::ffi_abi::FFI_DEFAULT_ABI,
+                          atypes.len(),
+                          result.as_raw_ptr(),
+                          atypes.as_mut_ptr())
+                .expect("low::prep_cif");
+        }
+
+        Cif {
+            cif: UnsafeCell::new(cif),
+            atypes: atypes,
+            args: args,
+            result: result,
+        }
+    }
+
+    /// Creates a new CIF for a variadic function taking `args` (the
+    /// first `nfixedargs` of which are the fixed arguments, the rest
+    /// being the variadic tail) and returning `result`.
+    pub fn new_variadic<I>(args: I, nfixedargs: usize, result: Type) -> Self
+        where I: IntoIterator<Item = Type>
+    {
+        let args: Vec<Type> = args.into_iter().collect();
+        let mut atypes: Box<[*mut low::ffi_type]>
+            = args.iter().map(Type::as_raw_ptr).collect();
+
+        let mut cif: low::ffi_cif = Default::default();
+        unsafe {
+            low::prep_cif_var(&mut cif,
+                              low::ffi_abi::FFI_DEFAULT_ABI,
+                              nfixedargs,
+                              atypes.len(),
+                              result.as_raw_ptr(),
+                              atypes.as_mut_ptr())
+                .expect("low::prep_cif_var");
+        }
+
+        Cif {
+            cif: UnsafeCell::new(cif),
+            atypes: atypes,
+            args: args,
+            result: result,
+        }
+    }
+
+    /// Gets a raw pointer to the underlying `ffi_cif`, for passing to
+    /// `low` functions. The returned pointer is valid only as long as
+    /// `self` is alive.
+    pub unsafe fn as_raw_ptr(&self) -> *mut low::ffi_cif {
+        self.cif.get()
+    }
+
+    /// Calls the function pointer `fun` with `args`, using this CIF's
+    /// argument and return types, yielding a result of type `R`.
+    ///
+    /// # Safety
+    ///
+    /// `fun` must be callable with the argument types this `Cif` was
+    /// built with, `args` must actually hold values of those types,
+    /// and `R` must match the `Cif`'s return type. Nothing here
+    /// checks any of that; getting it wrong reads or writes the
+    /// return value through a type it doesn't have, which is
+    /// undefined behavior.
+    pub unsafe fn call<R>(&self, fun: CodePtr, args: &[Arg]) -> R {
+        let mut arg_ptrs: Vec<*mut c_void>
+            = args.iter().map(Arg::as_ptr).collect();
+
+        low::call::<R>(self.as_raw_ptr(), fun, arg_ptrs.as_mut_ptr())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn call_with_zero_args() {
+        // Edge case at the other end of the arg-count range from the
+        // multi-arg tests elsewhere: an empty `atypes` array must
+        // still round-trip through `prep_cif`/`call` correctly.
+        extern "C" fn constant() -> u64 { 42 }
+
+        let cif = Cif::new(vec![], Type::u64());
+        let code = low::CodePtr::from_ptr(constant as *mut c_void);
+
+        let result: u64 = unsafe { cif.call(code, &[]) };
+
+        assert_eq!(42, result);
+    }
+}
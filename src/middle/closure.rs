@@ -0,0 +1,64 @@
+//! Owned closures.
+
+use std::marker::PhantomData;
+
+use low;
+use low::CodePtr;
+use middle::cif::Cif;
+
+/// An owned libffi closure.
+///
+/// Unlike `low::closure_alloc`/`low::closure_free`, a `Closure` frees
+/// its underlying `ffi_closure` automatically when dropped. It also
+/// owns the `Cif` it was built from, so callers don't need to keep a
+/// separate `Cif` alive alongside it.
+pub struct Closure<'a> {
+    alloc:   *mut low::ffi_closure,
+    code:    CodePtr,
+    _cif:    Cif,
+    _marker: PhantomData<&'a ()>,
+}
+
+impl<'a> Closure<'a> {
+    /// Allocates a new closure that calls `callback` with `userdata`
+    /// according to `cif`.
+    ///
+    /// # Safety
+    ///
+    /// `callback` must be a valid trampoline for `cif`'s argument and
+    /// return types, and must expect `userdata` as its userdata
+    /// pointer's pointee. Getting this wrong means the trampoline
+    /// reads arguments or userdata through the wrong type when the
+    /// resulting closure is called, which is undefined behavior.
+    pub unsafe fn new<U>(cif: Cif,
+                 callback: low::Callback<U>,
+                 userdata: &'a mut U) -> Self
+    {
+        let (alloc, code) = low::closure_alloc();
+
+        low::prep_closure_loc(alloc,
+                              cif.as_raw_ptr(),
+                              callback,
+                              userdata,
+                              code)
+            .expect("low::prep_closure_loc");
+
+        Closure {
+            alloc: alloc,
+            code: code,
+            _cif: cif,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Gets the C function pointer for calling this closure.
+    pub fn code_ptr(&self) -> &CodePtr {
+        &self.code
+    }
+}
+
+impl<'a> Drop for Closure<'a> {
+    fn drop(&mut self) {
+        unsafe { low::closure_free(self.alloc); }
+    }
+}
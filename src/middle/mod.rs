@@ -0,0 +1,18 @@
+//! A higher-level API for constructing CIFs and closures.
+//!
+//! Unlike the `low` layer, this layer takes care of keeping argument
+//! and return `Type`s alive for as long as the `Cif` that references
+//! them, and frees closures automatically when they are dropped. It
+//! does not, however, check that arguments passed to a `Cif` actually
+//! match its declared types. The `high` layer's typed closures avoid
+//! the question by building their `Cif` from Rust types.
+
+mod types;
+mod cif;
+mod arg;
+mod closure;
+
+pub use self::types::{Type, FfiType};
+pub use self::cif::Cif;
+pub use self::arg::Arg;
+pub use self::closure::Closure;
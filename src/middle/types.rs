@@ -0,0 +1,194 @@
+//! Native type descriptions.
+
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::ptr;
+use std::rc::Rc;
+
+use low;
+
+/// Represents a C type for the purposes of building a `Cif`.
+///
+/// A `Type` wraps the raw `ffi_type` descriptors used by libffi. The
+/// primitive types below are cheap to clone, merely wrapping a pointer
+/// to a `static`; an aggregate type built with `Type::structure` is
+/// reference-counted so that clones share the one underlying
+/// descriptor (and the element descriptors it points to stay alive
+/// for as long as any clone does).
+#[derive(Clone, Debug)]
+pub struct Type {
+    inner: Inner,
+}
+
+#[derive(Clone, Debug)]
+enum Inner {
+    Primitive(*mut low::ffi_type),
+    Structure(Rc<StructType>),
+}
+
+/// The owned pieces of an aggregate (`struct`) `ffi_type`: the header
+/// itself, the `NULL`-terminated array of element pointers it refers
+/// to, and the element `Type`s that those pointers point into.
+///
+/// The header is wrapped in an `UnsafeCell` because `prep_cif` writes
+/// `size`/`alignment` back through the raw pointer handed out by
+/// `as_raw_ptr`, and arbitrarily many `Type` clones can share this
+/// `StructType` via the surrounding `Rc` — mutating through a plain
+/// shared field would violate Rust's aliasing rules.
+struct StructType {
+    ffi_type: UnsafeCell<low::ffi_type>,
+    elements: Vec<*mut low::ffi_type>,
+    children: Vec<Type>,
+}
+
+impl fmt::Debug for StructType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("StructType")
+            .field("elements", &self.elements)
+            .field("children", &self.children)
+            .finish()
+    }
+}
+
+macro_rules! primitive_type {
+    ( $name:ident, $ffi_type:ident ) => {
+        /// Creates a `Type` representing the C type of the same name.
+        pub fn $name() -> Self {
+            unsafe { Self::from_raw(&mut low::$ffi_type) }
+        }
+    };
+}
+
+impl Type {
+    /// Wraps a raw `ffi_type` pointer as a `Type`. The pointee must
+    /// outlive any `Cif` built from the result.
+    pub unsafe fn from_raw(ffi_type: *mut low::ffi_type) -> Self {
+        Type { inner: Inner::Primitive(ffi_type) }
+    }
+
+    /// Gets the underlying `ffi_type`, for passing to `low::prep_cif`.
+    pub fn as_raw_ptr(&self) -> *mut low::ffi_type {
+        match self.inner {
+            Inner::Primitive(ptr) => ptr,
+            Inner::Structure(ref s) => s.ffi_type.get(),
+        }
+    }
+
+    /// Creates a `Type` describing a C `struct` whose members are
+    /// given by `elements`, in order.
+    ///
+    /// libffi fills in `size` and `alignment` itself when the `Type`
+    /// is used to build a `Cif`, so they are left zeroed here. The
+    /// element `Type`s are kept alive for as long as the returned
+    /// `Type` (or any of its clones) is, since libffi dereferences
+    /// `elements` lazily.
+    pub fn structure<I>(elements: I) -> Self
+        where I: IntoIterator<Item = Type>
+    {
+        let children: Vec<Type> = elements.into_iter().collect();
+
+        let mut elements: Vec<*mut low::ffi_type>
+            = children.iter().map(Type::as_raw_ptr).collect();
+        elements.push(ptr::null_mut());
+
+        let ffi_type = low::ffi_type {
+            size:      0,
+            alignment: 0,
+            type_:     low::FFI_TYPE_STRUCT as u16,
+            elements:  elements.as_mut_ptr(),
+        };
+
+        // Moving `elements`'s `Vec` below doesn't move its backing
+        // buffer, so the pointer just stashed in `ffi_type.elements`
+        // remains valid.
+        let structure = StructType {
+            ffi_type:  UnsafeCell::new(ffi_type),
+            elements:  elements,
+            children:  children,
+        };
+
+        Type { inner: Inner::Structure(Rc::new(structure)) }
+    }
+
+    primitive_type!(void, ffi_type_void);
+    primitive_type!(u8, ffi_type_uint8);
+    primitive_type!(i8, ffi_type_sint8);
+    primitive_type!(u16, ffi_type_uint16);
+    primitive_type!(i16, ffi_type_sint16);
+    primitive_type!(u32, ffi_type_uint32);
+    primitive_type!(i32, ffi_type_sint32);
+    primitive_type!(u64, ffi_type_uint64);
+    primitive_type!(i64, ffi_type_sint64);
+    primitive_type!(f32, ffi_type_float);
+    primitive_type!(f64, ffi_type_double);
+    primitive_type!(pointer, ffi_type_pointer);
+    primitive_type!(longdouble, ffi_type_longdouble);
+    primitive_type!(c32, ffi_type_complex_float);
+    primitive_type!(c64, ffi_type_complex_double);
+    primitive_type!(clongdouble, ffi_type_complex_longdouble);
+}
+
+/// Maps a Rust type onto the `Type` describing its C representation.
+///
+/// This lets generic code — such as `high::ClosureN::new` — build the
+/// `Cif` for a closure from its Rust argument and return types alone.
+pub trait FfiType {
+    /// The `Type` describing `Self`'s C representation.
+    fn reify() -> Type;
+}
+
+macro_rules! impl_ffi_type {
+    ( $rust_type:ty, $method:ident ) => {
+        impl FfiType for $rust_type {
+            fn reify() -> Type { Type::$method() }
+        }
+    };
+}
+
+impl_ffi_type!((), void);
+impl_ffi_type!(u8, u8);
+impl_ffi_type!(i8, i8);
+impl_ffi_type!(u16, u16);
+impl_ffi_type!(i16, i16);
+impl_ffi_type!(u32, u32);
+impl_ffi_type!(i32, i32);
+impl_ffi_type!(u64, u64);
+impl_ffi_type!(i64, i64);
+impl_ffi_type!(f32, f32);
+impl_ffi_type!(f64, f64);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::os::raw::c_void;
+
+    use low::CodePtr;
+    use middle::arg::Arg;
+    use middle::cif::Cif;
+
+    #[repr(C)]
+    struct Pair {
+        a: i32,
+        b: i32,
+    }
+
+    extern "C" fn sum_pair(pair: Pair) -> i32 {
+        pair.a + pair.b
+    }
+
+    #[test]
+    fn call_with_struct_by_value_arg() {
+        // Proves that `Type::structure`'s `FFI_TYPE_STRUCT` header and
+        // NULL-terminated `elements` array are laid out the way
+        // libffi expects, by round-tripping a real struct-by-value
+        // argument through a real C function.
+        let pair_type = Type::structure(vec![Type::i32(), Type::i32()]);
+        let cif = Cif::new(vec![pair_type], Type::i32());
+
+        let fun = CodePtr::from_ptr(sum_pair as *mut c_void);
+        let pair = Pair { a: 3, b: 4 };
+        let result: i32 = unsafe { cif.call(fun, &[Arg::new(&pair)]) };
+
+        assert_eq!(7, result);
+    }
+}